@@ -1,71 +1,172 @@
 use std::fmt::{self, Display, Formatter};
+use std::mem;
 
 #[cfg(feature = "parallel")]
 use rayon::prelude::*;
 
 use ::{CellWorld, RuleSet};
 
-/// Gibt potentielle eine Adjazenz zum Rand der Matrix an.
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
-enum Adjacency { // Bitflags vllt. besser hier?
-    Top,
-    Right,
-    Bottom,
-    Left,
-    TopRight,
-    TopLeft,
-    BottomRight,
-    BottomLeft,
-
-    None,
+/// Randbedingung: legt fest, welchen Wert eine außerhalb des Gitters liegende
+/// Nachbarzelle für die Zwecke von `field_matrix` annimmt.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Boundary<C> {
+    /// Jede Zelle außerhalb des Gitters hat den festen Wert `C` (der bisherige,
+    /// hart codierte `Default::default()`-Rand, jetzt aber konfigurierbar).
+    Fixed(C),
+    /// Das Gitter ist torusförmig: x und y wickeln modulo Breite/Höhe, sodass
+    /// z.b. Gleiter, die über den Rand laufen, auf der gegenüberliegenden Seite
+    /// wieder auftauchen.
+    Toroidal,
+    /// Das Gitter spiegelt sich am Rand: eine Zelle außerhalb wird an der
+    /// Randzeile/-spalte gespiegelt zurück ins Gitter abgebildet.
+    Reflective,
 }
 
-/// Eine Gitter-Engine, welche Werte am Rand mit Default::default() emuliert.
-#[derive(Clone, Debug, Eq, PartialEq)]
+impl<C: Default> Default for Boundary<C> {
+    fn default() -> Self {
+        Boundary::Fixed(C::default())
+    }
+}
+
+/// Zellentypen, die sich verlustfrei in den (ggf. erweiterten) RLE-Zeichensatz
+/// hinein und aus ihm heraus abbilden lassen, damit `Driver::from_rle`/`to_rle`
+/// damit arbeiten können. Für zweiwertige Zellen genügt `b`/`o`; mehrwertige
+/// Zellen (z.b. `rulesets::GenCell`) nutzen zusätzlich die erweiterten
+/// Zustands-Zeichen `.`, `A`, `B`, ...
+pub trait RleCell: Sized {
+    /// Wandle die Zelle in ihr RLE-Zeichen um.
+    fn to_rle_char(&self) -> char;
+
+    /// Wandle ein RLE-Zeichen in die zugehörige Zelle um, falls es gültig ist.
+    fn from_rle_char(token: char) -> Option<Self>;
+}
+
+/// Fehler beim Einlesen eines RLE-Patterns via `Driver::from_rle`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RleError {
+    /// Die Header-Zeile (`x = .., y = ..`) fehlte oder ließ sich nicht parsen.
+    MissingHeader,
+    /// Ein Zeichen im Datenteil war kein gültiges Lauflängen-Token für diesen Zellentyp.
+    InvalidToken(char),
+}
+
+impl Display for RleError {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        match *self {
+            RleError::MissingHeader => write!(fmt, "fehlende oder ungültige 'x = .., y = ..'-Header-Zeile"),
+            RleError::InvalidToken(c) => write!(fmt, "'{}' ist kein gültiges RLE-Token", c),
+        }
+    }
+}
+
+impl ::std::error::Error for RleError {}
+
+/// Rufe den Wert der Zelle an der geg. (ggf. außerhalb des Gitters liegenden)
+/// Position in `data` gemäß der geg. `Boundary` ab. Freie Funktion statt
+/// Methode, damit sie in `Driver::step` nur `data`/`boundary` ausleiht und
+/// sich nicht mit einem gleichzeitigen mutable Borrow von `back` überschneidet.
+fn sample<C: Copy>(data: &[C], width: usize, height: usize, boundary: &Boundary<C>, x: isize, y: isize) -> C {
+    let width_i = width as isize;
+    let height_i = height as isize;
+
+    if x >= 0 && x < width_i && y >= 0 && y < height_i {
+        return data[(y as usize) * width + (x as usize)];
+    }
+
+    match *boundary {
+        Boundary::Fixed(value) => value,
+        Boundary::Toroidal => data[(y.rem_euclid(height_i) as usize) * width + (x.rem_euclid(width_i) as usize)],
+        Boundary::Reflective => {
+            let cx = x.max(0).min(width_i - 1) as usize;
+            let cy = y.max(0).min(height_i - 1) as usize;
+            data[cy * width + cx]
+        }
+    }
+}
+
+/// Holt die 3x3-Matrix um die Zelle mit geg. Index in `data` (siehe `sample`).
+fn field_matrix<C: Copy>(data: &[C], width: usize, height: usize, boundary: &Boundary<C>, idx: usize) -> [[C; 3]; 3] {
+    let x = (idx % width) as isize;
+    let y = (idx / width) as isize;
+
+    [
+        [sample(data, width, height, boundary, x - 1, y - 1), sample(data, width, height, boundary, x, y - 1), sample(data, width, height, boundary, x + 1, y - 1)],
+        [sample(data, width, height, boundary, x - 1, y), data[idx], sample(data, width, height, boundary, x + 1, y)],
+        [sample(data, width, height, boundary, x - 1, y + 1), sample(data, width, height, boundary, x, y + 1), sample(data, width, height, boundary, x + 1, y + 1)],
+    ]
+}
+
+/// Eine Gitter-Engine mit konfigurierbarer Randbedingung (siehe `Boundary`)
+/// und einer zur Laufzeit konfigurierbaren Regel-Instanz. Hält zwei
+/// gleich große Puffer (`data`/`back`), zwischen denen pro Schritt nur
+/// getauscht statt neu alloziert wird (siehe `CellWorld::step`).
+#[derive(Clone)]
 pub struct Driver<R: RuleSet> {
     data: Vec<R::Cell>,
+    back: Vec<R::Cell>,
     width: usize,
+    boundary: Boundary<R::Cell>,
+    rule: R,
+}
+
+// `back` ist reiner Scratch-Space für `step` und enthält je nach Historie
+// unterschiedlich stale Daten, auch wenn zwei `Driver` über `data`/`width`/
+// `boundary`/`rule` denselben beobachtbaren Zustand haben. `PartialEq`/`Debug`
+// werden daher von Hand implementiert und lassen `back` aus, statt es
+// abzuleiten.
+impl<R: RuleSet> PartialEq for Driver<R>
+        where R: PartialEq {
+    fn eq(&self, other: &Self) -> bool {
+        self.data == other.data
+            && self.width == other.width
+            && self.boundary == other.boundary
+            && self.rule == other.rule
+    }
+}
+
+impl<R: RuleSet> fmt::Debug for Driver<R>
+        where R: fmt::Debug, R::Cell: fmt::Debug {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        fmt.debug_struct("Driver")
+            .field("data", &self.data)
+            .field("width", &self.width)
+            .field("boundary", &self.boundary)
+            .field("rule", &self.rule)
+            .finish()
+    }
 }
 
 impl<R: RuleSet> Driver<R> {
-    /// Erstelle neuen Driver mit geg. Breite und Höhe.
-    fn new_with(width: usize, height: usize) -> Self {
+    /// Erstelle neuen Driver mit geg. Breite und Höhe, der `Default`-
+    /// Randbedingung (`Boundary::Fixed(Default::default())`) und der
+    /// `Default`-Instanz der Regel.
+    fn new_with(width: usize, height: usize) -> Self
+            where R: Default {
+        Self::with_boundary(width, height, Boundary::default())
+    }
+
+    /// Erstelle neuen Driver mit geg. Breite, Höhe und Randbedingung, unter
+    /// Verwendung der `Default`-Instanz der Regel.
+    pub fn with_boundary(width: usize, height: usize, boundary: Boundary<R::Cell>) -> Self
+            where R: Default {
+        Self::with_rule(width, height, boundary, R::default())
+    }
+
+    /// Erstelle neuen Driver mit geg. Breite, Höhe, Randbedingung und
+    /// konkreter Regel-Instanz (z.b. eine zur Laufzeit geparste `LifeLike`-Regel).
+    pub fn with_rule(width: usize, height: usize, boundary: Boundary<R::Cell>, rule: R) -> Self {
         Self {
             data: (0..(width * height)).map(|_| Default::default()).collect(),
+            back: (0..(width * height)).map(|_| Default::default()).collect(),
             width,
+            boundary,
+            rule,
         }
     }
 
-    /// Berechnet, ob die Zelle am gegebenen Index adjazent zu einem der Ränder ist.
-    fn adjacency(&self, idx: usize) -> Adjacency {
-        let x = idx % self.width;
-        let y = idx / self.width;
-
-        if y == 0 {
-            if x == 0 {
-                Adjacency::TopLeft
-            } else if x == self.width - 1 {
-                Adjacency::TopRight
-            } else {
-                Adjacency::Top
-            }
-        } else if y == (self.data.len() / self.width) - 1 {
-            if x == 0 {
-                Adjacency::BottomLeft
-            } else if x == self.width - 1 {
-                Adjacency::BottomRight
-            } else {
-                Adjacency::Bottom
-            }
-        } else {
-            if x == 0 {
-                Adjacency::Left
-            } else if x == self.width - 1 {
-                Adjacency::Right
-            } else {
-                Adjacency::None
-            }
-        }
+    /// Die Höhe des Gitters.
+    fn height(&self) -> usize {
+        self.data.len() / self.width
     }
 
     /// Rufe Wert einer Zelle ab.
@@ -73,58 +174,120 @@ impl<R: RuleSet> Driver<R> {
         self.data[y * self.width + x]
     }
 
-    /// Holt die 3x3-Matrix um die Zelle mit geg. Index.
-    fn get_field_matrix(&self, idx: usize) -> [[R::Cell; 3]; 3] {
-        let adj = self.adjacency(idx);
-        let tl = match adj {
-            Adjacency::Top | Adjacency::TopLeft | Adjacency::TopRight | Adjacency::Left => Default::default(),
-            _ => self.data[idx - self.width - 1],
-        };
-        let t = match adj {
-            Adjacency::Top | Adjacency::TopLeft | Adjacency::TopRight => Default::default(),
-            _ => self.data[idx - self.width],
-        };
-        let tr = match adj {
-            Adjacency::Top | Adjacency::TopLeft | Adjacency::TopRight | Adjacency::Right => Default::default(),
-            _ => self.data[idx - self.width + 1],
-        };
-        let l = match adj {
-            Adjacency::TopLeft | Adjacency::BottomLeft | Adjacency::Left => Default::default(),
-            _ => self.data[idx - 1],
-        };
-        let c = self.data[idx];
-        let r = match adj {
-            Adjacency::TopRight | Adjacency::BottomRight | Adjacency::Right => Default::default(),
-            _ => self.data[idx + 1],
-        };
-        let bl = match adj {
-            Adjacency::Bottom | Adjacency::BottomLeft | Adjacency::BottomRight | Adjacency::Left => Default::default(),
-            _ => self.data[idx + self.width - 1],
-        };
-        let b = match adj {
-            Adjacency::Bottom | Adjacency::BottomLeft | Adjacency::BottomRight => Default::default(),
-            _ => self.data[idx + self.width],
-        };
-        let br = match adj {
-            Adjacency::Bottom | Adjacency::BottomLeft | Adjacency::BottomRight | Adjacency::Right => Default::default(),
-            _ => self.data[idx + self.width + 1],
-        };
-
-        [
-            [tl, t, tr],
-            [l, c, r],
-            [bl, b, br],
-        ]
-    }
-
     /// Setze Wert einer Zelle.
     fn set(&mut self, x: usize, y: usize, value: R::Cell) {
         self.data[y * self.width + x] = value;
     }
 }
 
+impl<R: RuleSet> Driver<R>
+        where R::Cell: RleCell {
+    /// Lies ein RLE-Pattern (das Standard-Lauflängenkodierungsformat für
+    /// Life-artige Pattern-Dateien, z.b. `"x = 3, y = 3\n3o$bob$!"`) in einen
+    /// neuen `Driver` mit der `Default`-Instanz der Regel ein. Kommentarzeilen
+    /// (beginnend mit `#`) werden übersprungen, die Gittergröße wird dem
+    /// Header entnommen.
+    pub fn from_rle(rle: &str) -> Result<Self, RleError>
+            where R: Default {
+        let mut lines = rle.lines().filter(|line| !line.trim_start().starts_with('#'));
+        let header = lines.next().ok_or(RleError::MissingHeader)?;
+        let (width, height) = Self::parse_rle_header(header)?;
+
+        let mut driver = Self::new_with(width, height);
+
+        let mut x = 0;
+        let mut y = 0;
+        let mut run: String = String::new();
+
+        'data: for line in lines {
+            for token in line.chars() {
+                match token {
+                    '!' => break 'data,
+                    '$' => {
+                        y += run.parse().unwrap_or(1);
+                        x = 0;
+                        run.clear();
+                    }
+                    c if c.is_ascii_digit() => run.push(c),
+                    c => {
+                        let count: usize = run.parse().unwrap_or(1);
+                        run.clear();
+
+                        let cell = R::Cell::from_rle_char(c).ok_or(RleError::InvalidToken(c))?;
+                        for _ in 0..count {
+                            if x < width && y < height {
+                                driver.set(x, y, cell);
+                            }
+                            x += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(driver)
+    }
+
+    /// Parse die `"x = W, y = H"`-Header-Zeile eines RLE-Patterns (ggf. mit
+    /// weiteren, hier ignorierten Feldern wie `rule = ..`).
+    fn parse_rle_header(header: &str) -> Result<(usize, usize), RleError> {
+        let mut width = None;
+        let mut height = None;
+
+        for field in header.split(',') {
+            let mut kv = field.splitn(2, '=');
+            let key = kv.next().unwrap_or("").trim();
+            let value = kv.next().unwrap_or("").trim();
+
+            match key {
+                "x" => width = value.parse().ok(),
+                "y" => height = value.parse().ok(),
+                _ => {}
+            }
+        }
+
+        match (width, height) {
+            (Some(w), Some(h)) => Ok((w, h)),
+            _ => Err(RleError::MissingHeader),
+        }
+    }
+
+    /// Gib den aktuellen Gitterinhalt als RLE-Pattern aus, mit kompakten
+    /// Lauflängen statt eines Zeichens pro Zelle.
+    pub fn to_rle(&self) -> String {
+        let height = self.height();
+        let mut out = format!("x = {}, y = {}\n", self.width, height);
+
+        for row in 0..height {
+            let base = row * self.width;
+            let cells = &self.data[base..(base + self.width)];
+
+            let mut idx = 0;
+            while idx < cells.len() {
+                let run_start = idx;
+                while idx < cells.len() && cells[idx] == cells[run_start] {
+                    idx += 1;
+                }
+
+                let run_len = idx - run_start;
+                if run_len > 1 {
+                    out.push_str(&run_len.to_string());
+                }
+                out.push(cells[run_start].to_rle_char());
+            }
+
+            if row + 1 < height {
+                out.push('$');
+            }
+        }
+
+        out.push('!');
+        out
+    }
+}
+
 #[cfg(not(feature = "parallel"))]
-impl<R: RuleSet> CellWorld<R> for Driver<R> {
+impl<R: RuleSet + Default> CellWorld<R> for Driver<R> {
     /// Leg ein neues Gitter mit der angegebenen Höhe und Breite an.
     /// Alle Zellen werden mit Default::default() initialisiert.
     fn new(width: usize, height: usize) -> Self {
@@ -143,17 +306,24 @@ impl<R: RuleSet> CellWorld<R> for Driver<R> {
         self.get(x, y)
     }
 
-    /// Wende das Ruleset einmal auf das ganze Gitter an.
+    /// Wende das Ruleset einmal auf das ganze Gitter an. Schreibt die nächste
+    /// Generation in den `back`-Puffer und tauscht ihn anschließend mit
+    /// `data`, statt pro Schritt einen neuen `Vec` zu allozieren.
     fn step(&mut self) {
-        self.data = (0..self.data.len())
-            .map(|idx| R::step(self.get_field_matrix(idx)))
-            .collect();
+        let Driver { ref data, ref mut back, width, ref boundary, ref rule } = *self;
+        let height = data.len() / width;
+
+        for (idx, cell) in back.iter_mut().enumerate() {
+            *cell = rule.step(field_matrix(data, width, height, boundary, idx));
+        }
+
+        mem::swap(&mut self.data, &mut self.back);
     }
 }
 
 #[cfg(feature = "parallel")]
-impl<R: RuleSet> CellWorld<R> for Driver<R>
-        where R::Cell: Send + Sync {
+impl<R: RuleSet + Default> CellWorld<R> for Driver<R>
+        where R::Cell: Send + Sync, R: Send + Sync {
     /// Leg ein neues Gitter mit der angegebenen Höhe und Breite an.
     /// Alle Zellen werden mit Default::default() initialisiert.
     fn new(width: usize, height: usize) -> Self {
@@ -172,11 +342,18 @@ impl<R: RuleSet> CellWorld<R> for Driver<R>
         self.get(x, y)
     }
 
-    /// Wende das Ruleset einmal auf das ganze Gitter an.
+    /// Wende das Ruleset einmal auf das ganze Gitter an. Schreibt die nächste
+    /// Generation parallel in den `back`-Puffer und tauscht ihn anschließend
+    /// mit `data`, statt pro Schritt einen neuen `Vec` zu allozieren.
     fn step(&mut self) {
-        self.data = (0..self.data.len()).into_par_iter()
-            .map(|idx| R::step(self.get_field_matrix(idx)))
-            .collect();
+        let Driver { ref data, ref mut back, width, ref boundary, ref rule } = *self;
+        let height = data.len() / width;
+
+        back.par_iter_mut().enumerate().for_each(|(idx, cell)| {
+            *cell = rule.step(field_matrix(data, width, height, boundary, idx));
+        });
+
+        mem::swap(&mut self.data, &mut self.back);
     }
 }
 
@@ -195,7 +372,7 @@ impl<R: RuleSet> Display for Driver<R>
 #[cfg(test)]
 mod tests {
     use super::*;
-    use rulesets::{BinaryCell, Diffusion, GameOfLife};
+    use rulesets::{BinaryCell, Diffusion, GameOfLife, GenCell, Generations};
 
     #[test]
     fn test_diffusion() {
@@ -260,4 +437,103 @@ mod tests {
         assert_eq!(gol2.get_cell(1, 0), BinaryCell::Live);
         assert_eq!(gol2.get_cell(1, 1), BinaryCell::Live);
     }
+
+    #[test]
+    fn test_toroidal_boundary_wraps_neighbors() {
+        // Drei lebende Zellen, so platziert, dass die untere rechte Ecke (2, 2)
+        // nur über den Umlauf um beide Ränder drei lebende Nachbarn sieht
+        // (je einen über den rechten/unteren Rand gewickelt auf (1, 0) und
+        // (0, 0), sowie die in-grid-Zelle (1, 1)) und deshalb geboren wird.
+        let mut toroidal: Driver<GameOfLife> = Driver::with_boundary(3, 3, Boundary::Toroidal);
+        toroidal.set_cell(0, 0, BinaryCell::Live);
+        toroidal.set_cell(1, 0, BinaryCell::Live);
+        toroidal.set_cell(1, 1, BinaryCell::Live);
+        toroidal.step();
+
+        assert_eq!(toroidal.get_cell(2, 2), BinaryCell::Live);
+
+        // Mit festem (totem) Rand fehlen dieselben gewickelten Nachbarn, die
+        // Zelle hat dann nur einen lebenden Nachbarn und bleibt tot.
+        let mut fixed: Driver<GameOfLife> = Driver::with_boundary(3, 3, Boundary::Fixed(BinaryCell::Dead));
+        fixed.set_cell(0, 0, BinaryCell::Live);
+        fixed.set_cell(1, 0, BinaryCell::Live);
+        fixed.set_cell(1, 1, BinaryCell::Live);
+        fixed.step();
+
+        assert_eq!(fixed.get_cell(2, 2), BinaryCell::Dead);
+    }
+
+    #[test]
+    fn test_reflective_boundary_mirrors_edge() {
+        // Einzelne lebende Zelle in der Ecke (0, 0): unter `Reflective` spiegeln
+        // drei der acht Nachbarplätze (TL, T, L) auf die Zelle selbst zurück,
+        // liefern also dreimal ihren eigenen (lebenden) Wert, sodass sie als
+        // hätte sie drei lebende Nachbarn überlebt (bzw. geboren wird).
+        let mut reflective: Driver<GameOfLife> = Driver::with_boundary(3, 3, Boundary::Reflective);
+        reflective.set_cell(0, 0, BinaryCell::Live);
+        reflective.step();
+
+        assert_eq!(reflective.get_cell(0, 0), BinaryCell::Live);
+
+        // Mit festem (totem) Rand sieht dieselbe Zelle keine Nachbarn und stirbt.
+        let mut fixed: Driver<GameOfLife> = Driver::with_boundary(3, 3, Boundary::Fixed(BinaryCell::Dead));
+        fixed.set_cell(0, 0, BinaryCell::Live);
+        fixed.step();
+
+        assert_eq!(fixed.get_cell(0, 0), BinaryCell::Dead);
+    }
+
+    #[test]
+    fn test_rle_roundtrip_glider() {
+        // Standard-Gleiter, wie er in den meisten .rle-Bibliotheken auftaucht.
+        let glider: Driver<GameOfLife> = Driver::from_rle("x = 3, y = 3\nbob$2bo$3o!").unwrap();
+
+        assert_eq!(glider.get_cell(0, 0), BinaryCell::Dead);
+        assert_eq!(glider.get_cell(1, 0), BinaryCell::Live);
+        assert_eq!(glider.get_cell(2, 0), BinaryCell::Dead);
+        assert_eq!(glider.get_cell(0, 1), BinaryCell::Dead);
+        assert_eq!(glider.get_cell(1, 1), BinaryCell::Dead);
+        assert_eq!(glider.get_cell(2, 1), BinaryCell::Live);
+        assert_eq!(glider.get_cell(0, 2), BinaryCell::Live);
+        assert_eq!(glider.get_cell(1, 2), BinaryCell::Live);
+        assert_eq!(glider.get_cell(2, 2), BinaryCell::Live);
+
+        let roundtripped = Driver::<GameOfLife>::from_rle(&glider.to_rle()).unwrap();
+        for y in 0..3 {
+            for x in 0..3 {
+                assert_eq!(roundtripped.get_cell(x, y), glider.get_cell(x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn test_rle_roundtrip_generations() {
+        // Erweiterte RLE-Zustands-Zeichen ('.', 'A', 'B', ...) für ein
+        // mehrwertiges Ruleset (siehe `GenCell::to_rle_char`/`from_rle_char`).
+        let pattern: Driver<Generations> = Driver::from_rle("x = 3, y = 2\n.AB$B.A!").unwrap();
+
+        assert_eq!(pattern.get_cell(0, 0), GenCell(0));
+        assert_eq!(pattern.get_cell(1, 0), GenCell(1));
+        assert_eq!(pattern.get_cell(2, 0), GenCell(2));
+        assert_eq!(pattern.get_cell(0, 1), GenCell(2));
+        assert_eq!(pattern.get_cell(1, 1), GenCell(0));
+        assert_eq!(pattern.get_cell(2, 1), GenCell(1));
+
+        let roundtripped = Driver::<Generations>::from_rle(&pattern.to_rle()).unwrap();
+        for y in 0..2 {
+            for x in 0..3 {
+                assert_eq!(roundtripped.get_cell(x, y), pattern.get_cell(x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn test_rle_invalid_header() {
+        assert_eq!(Driver::<GameOfLife>::from_rle("not a header\nbo!"), Err(RleError::MissingHeader));
+    }
+
+    #[test]
+    fn test_rle_invalid_token() {
+        assert_eq!(Driver::<GameOfLife>::from_rle("x = 1, y = 1\nx!"), Err(RleError::InvalidToken('x')));
+    }
 }
\ No newline at end of file
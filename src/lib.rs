@@ -15,8 +15,12 @@ pub trait RuleSet {
     ///  [ L, M,  R],
     ///  [BL, B, BR]]    (Sprich: row-major-order)
     ///
-    /// Der Rückgabewert ist der neue Wert für die mittlere Zelle
-    fn step(neighborhood: [[<Self as RuleSet>::Cell; 3]; 3]) -> <Self as RuleSet>::Cell;
+    /// Der Rückgabewert ist der neue Wert für die mittlere Zelle.
+    ///
+    /// Nimmt `&self`, damit Regeln (wie `rulesets::LifeLike`) ihr Verhalten zur
+    /// Laufzeit konfigurieren können, statt es als reinen Typ-Parameter fest
+    /// zu verdrahten; der `Driver` hält dazu eine Instanz von `Self`.
+    fn step(&self, neighborhood: [[<Self as RuleSet>::Cell; 3]; 3]) -> <Self as RuleSet>::Cell;
 }
 
 pub trait CellWorld<R: RuleSet> {
@@ -1,42 +1,13 @@
-pub trait RuleSet {
-    /// Der diesem RuleSet zugrunde liegende Zellentyp
-    type Cell : Default + Copy + PartialEq;
-
-    /// Die Regel, die angibt, wie sich die Zellen in diesem RuleSet verhalten.
-    /// Das übergebene Array gibt den Zustand einer Zelle und ihrer acht Moore-Nachbarn
-    /// (https://de.wikipedia.org/wiki/Moore-Nachbarschaft) wie folgt an:
-    /// [[TL, T, TR].
-    ///  [ L, M,  R],
-    ///  [BL, B, BR]]    (Sprich: row-major-order)
-    ///
-    /// Der Rückgabewert ist der neue Wert für die mittlere Zelle
-    fn step(neighborhood: [[<Self as RuleSet>::Cell; 3]; 3]) -> <Self as RuleSet>::Cell;
-}
-
-pub trait CellWorld<R: RuleSet> {
-    /// Leg ein neues Gitter mit der angegebenen Höhe und Breite an.
-    /// Alle Zellen werden mit Default::default() initialisiert.
-    fn new(width: usize, height: usize) -> Self;
-
-    /// Setz den Wert der Zelle an der angegebenen Position auf `value`
-    /// Bei Koordinaten außerhalb des Gitters: beliebiges, safes Verhalten (z.b. panic, no-op)
-    fn set_cell(&mut self, x: usize, y: usize, value: R::Cell);
-
-    /// Gib der Wert der Zelle an der angegebenen Position aus.
-    /// Bei Koordinaten außerhalb des Gitters: beliebiges, safes Verhalten (z.b. panic, beliebiger return value)
-    fn get_cell(&self, x: usize, y: usize) -> R::Cell;
-
-    /// Wende das Ruleset einmal auf das ganze Gitter an.
-    fn step(&mut self);
-
-    /// Wende das Ruleset `n`-mal auf das ganze Gitter an.
-    /// Falls dir keine tollen Optimisationen einfallen, gibt es eine simple default-Implementation
-    fn step_many(&mut self, n: usize) {
-        for _ in 0..n {
-            self.step();
-        }
-    }
-}
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+use std::marker::PhantomData;
+use std::sync::Mutex;
+
+use once_cell::sync::OnceCell;
+
+use ::RuleSet;
+use ::drivers::RleCell;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum BinaryCell {
@@ -48,14 +19,31 @@ impl Default for BinaryCell {
     fn default() -> Self { BinaryCell::Dead }
 }
 
+impl RleCell for BinaryCell {
+    fn to_rle_char(&self) -> char {
+        match *self {
+            BinaryCell::Dead => 'b',
+            BinaryCell::Live => 'o',
+        }
+    }
+
+    fn from_rle_char(token: char) -> Option<Self> {
+        match token {
+            'b' | '.' => Some(BinaryCell::Dead),
+            'o' => Some(BinaryCell::Live),
+            _ => None,
+        }
+    }
+}
+
 // das altbekannte Conway's Game of Life
-#[derive(Debug)]
+#[derive(Debug, Default, PartialEq, Eq)]
 pub struct GameOfLife;
 
 impl RuleSet for GameOfLife {
     type Cell = self::BinaryCell;
 
-    fn step([[tl, t, tr],
+    fn step(&self, [[tl, t, tr],
                 [l,  m, r ],
                 [bl, b, br]]: [[BinaryCell; 3]; 3]) -> BinaryCell {
         use self::BinaryCell::*;
@@ -69,17 +57,410 @@ impl RuleSet for GameOfLife {
 }
 
 // sehr simple Simulation einer Diffusion
-#[derive(Debug)]
+#[derive(Debug, Default, PartialEq)]
 pub struct Diffusion;
 
 impl RuleSet for Diffusion {
     type Cell = f32;
 
-    fn step([[tl, t, tr],
+    fn step(&self, [[tl, t, tr],
                 [l,  m, r ],
                 [bl, b, br]]: [[f32; 3]; 3]) -> f32 {
         0.05*tl + 0.1*t + 0.05*tr +
         0.1 * l + 0.4*m + 0.1 * r +
         0.05*bl + 0.1*b + 0.05*br
     }
-}
\ No newline at end of file
+}
+
+/// Fehler beim Parsen einer B/S-Regelangabe (`LifeLike`, `Generations`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// Die Regel enthielt nicht den erwarteten `B`-, `S`- oder `C`-Teil, getrennt durch `/`.
+    MissingSection(char),
+    /// Eine der Ziffern im B- oder S-Teil war keine gültige Nachbarnanzahl (0-8).
+    InvalidDigit(char),
+    /// Der `C`-Teil war keine gültige Zustandsanzahl (ganzzahlig, >= 2).
+    InvalidStateCount(String),
+}
+
+impl Display for ParseError {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        match *self {
+            ParseError::MissingSection(c) => write!(fmt, "fehlender '{}'-Abschnitt in der Regelangabe", c),
+            ParseError::InvalidDigit(c) => write!(fmt, "'{}' ist keine gültige Nachbarnanzahl (0-8)", c),
+            ParseError::InvalidStateCount(ref s) => write!(fmt, "'{}' ist keine gültige Zustandsanzahl (>= 2)", s),
+        }
+    }
+}
+
+impl ::std::error::Error for ParseError {}
+
+/// Parst den `B`- oder `S`-Teil einer Birth/Survival-Regelangabe (z.b. `"B3"`
+/// oder `"S23"`) in die Menge der Nachbarnanzahlen, bei denen die Regel greift.
+fn parse_neighbor_set(section: &str, prefix: char) -> Result<[bool; 9], ParseError> {
+    if !section.starts_with(prefix) {
+        return Err(ParseError::MissingSection(prefix));
+    }
+
+    let mut set = [false; 9];
+    for c in section[1..].chars() {
+        let n = c.to_digit(10).ok_or(ParseError::InvalidDigit(c))? as usize;
+        if n > 8 {
+            return Err(ParseError::InvalidDigit(c));
+        }
+
+        set[n] = true;
+    }
+
+    Ok(set)
+}
+
+/// Laufzeit-konfigurierbare Life-like-Regel, angegeben in der üblichen
+/// Birth/Survival-Notation, z.b. `"B3/S23"` (Conway), `"B36/S23"` (HighLife)
+/// oder `"B2/S"` (Seeds). `birth`/`survival` sind dabei, indiziert mit der
+/// Anzahl lebender Moore-Nachbarn, angeben ob eine tote bzw. lebende Zelle
+/// in diesem Fall lebt.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LifeLike {
+    birth: [bool; 9],
+    survival: [bool; 9],
+}
+
+impl LifeLike {
+    /// Parse eine B/S-Regelangabe wie `"B3/S23"` in eine `LifeLike`-Regel.
+    pub fn parse(s: &str) -> Result<Self, ParseError> {
+        let mut sections = s.splitn(2, '/');
+        let b = sections.next().ok_or(ParseError::MissingSection('B'))?;
+        let s = sections.next().ok_or(ParseError::MissingSection('S'))?;
+
+        Ok(LifeLike {
+            birth: parse_neighbor_set(b, 'B')?,
+            survival: parse_neighbor_set(s, 'S')?,
+        })
+    }
+}
+
+impl RuleSet for LifeLike {
+    type Cell = self::BinaryCell;
+
+    fn step(&self, [[tl, t, tr],
+                [l,  m, r ],
+                [bl, b, br]]: [[BinaryCell; 3]; 3]) -> BinaryCell {
+        use self::BinaryCell::*;
+        let live_neighbors = [tl, t, tr, l, r, bl, b, br].iter().filter(|&&x| x == Live).count();
+        match m {
+            Dead if self.birth[live_neighbors] => Live,
+            Live if self.survival[live_neighbors] => Live,
+            _ => Dead,
+        }
+    }
+}
+
+/// Packt eine 3x3-Nachbarschaft zweiwertiger Zellen in den 9-Bit-Index der
+/// Übergangstabelle von `TabulatedRule` (Bit-Reihenfolge wie die row-major
+/// `[TL, T, TR, L, M, R, BL, B, BR]`-Auflistung).
+fn pack_neighborhood(neighborhood: [[BinaryCell; 3]; 3]) -> u16 {
+    neighborhood.iter().flat_map(|row| row.iter())
+        .enumerate()
+        .fold(0u16, |mask, (i, &cell)| {
+            if cell == BinaryCell::Live { mask | (1 << i) } else { mask }
+        })
+}
+
+/// Kehrt `pack_neighborhood` um: entpackt einen 9-Bit-Index wieder in die
+/// zugehörige 3x3-Nachbarschaft.
+fn unpack_neighborhood(mask: u16) -> [[BinaryCell; 3]; 3] {
+    let bit = |i: u16| if mask & (1 << i) != 0 { BinaryCell::Live } else { BinaryCell::Dead };
+
+    [
+        [bit(0), bit(1), bit(2)],
+        [bit(3), bit(4), bit(5)],
+        [bit(6), bit(7), bit(8)],
+    ]
+}
+
+/// Holt die (einmalig pro Regel-*Typ* berechnete) 512-Einträge-Übergangstabelle
+/// der `Default`-Instanz von `R`. Ein `static` in einer generischen Funktion
+/// existiert nur einmal für alle `R`; die Tabellen liegen daher, nach der
+/// `TypeId` von `R` getrennt, in einer gemeinsamen Map. Jede Tabelle wird so
+/// nur einmal pro Regel-Typ berechnet und danach von allen
+/// `TabulatedRule::<R>::default()`-Aufrufen (d.h. allen `Driver`s dieses Typs)
+/// geteilt. Nur für den `Default`-Fall sinnvoll: hier gibt es pro Typ nur
+/// einen möglichen Regel-Wert (z.b. `GameOfLife`, ein Unit-Struct), die
+/// Tabelle lässt sich also unbedenklich typweit cachen. Laufzeit-konfigurierte
+/// Regeln (z.b. ein geparstes `LifeLike`) haben dagegen je nach Instanz
+/// unterschiedliche Birth/Survival-Mengen und müssen individuell über
+/// `from_rule` tabelliert werden (siehe dort) — sie über diesen Typ-weiten
+/// Cache zu teilen würde den ursprünglichen Bug wieder einführen, bei dem
+/// geparste Regeln durch `R::default()` ersetzt wurden.
+fn shared_default_table<R>() -> &'static [BinaryCell; 512]
+        where R: RuleSet<Cell = BinaryCell> + Default + 'static {
+    static TABLES: OnceCell<Mutex<HashMap<TypeId, &'static [BinaryCell; 512]>>> = OnceCell::new();
+
+    let mut tables = TABLES.get_or_init(Default::default).lock().unwrap();
+    *tables.entry(TypeId::of::<R>()).or_insert_with(|| {
+        let rule = R::default();
+        let mut table = [BinaryCell::Dead; 512];
+        for mask in 0..512u16 {
+            table[mask as usize] = rule.step(unpack_neighborhood(mask));
+        }
+        Box::leak(Box::new(table))
+    })
+}
+
+/// Adapter, der für ein zweiwertiges `RuleSet` alle `2^9` möglichen
+/// Nachbarschaften durchrechnet und in einer 512-Einträge-Tabelle ablegt.
+/// `step` packt die Nachbarschaft danach nur noch in einen `u16`-Index und
+/// liest das Ergebnis per Tabellen-Lookup aus, statt die Regel jedes Mal neu
+/// auszuwerten — ein deutlicher Speedup für dichte Game-of-Life-artige
+/// Gitter.
+#[derive(Clone, Debug)]
+pub struct TabulatedRule<R> {
+    table: [BinaryCell; 512],
+    _marker: PhantomData<R>,
+}
+
+impl<R> TabulatedRule<R>
+        where R: RuleSet<Cell = BinaryCell> {
+    /// Materialisiert die 512-Einträge-Übergangstabelle aus der geg.
+    /// Regel-*Instanz*, indem `rule.step` einmal auf jede der `2^9` möglichen
+    /// Nachbarschaften angewendet wird. Für zur Laufzeit konfigurierte Regeln
+    /// (z.b. ein geparstes `LifeLike`) gedacht, deren Birth/Survival-Mengen im
+    /// Instanzwert stecken statt im Typ — die Tabelle wird daher pro Aufruf
+    /// frisch berechnet und nicht typweit geteilt (anders als der
+    /// `Default`-Fall, siehe `shared_default_table`).
+    pub fn from_rule(rule: &R) -> Self {
+        let mut table = [BinaryCell::Dead; 512];
+        for mask in 0..512u16 {
+            table[mask as usize] = rule.step(unpack_neighborhood(mask));
+        }
+
+        TabulatedRule { table, _marker: PhantomData }
+    }
+}
+
+impl<R> Default for TabulatedRule<R>
+        where R: RuleSet<Cell = BinaryCell> + Default + 'static {
+    /// Nutzt die typweit gecachte Tabelle der `Default`-Instanz von `R`
+    /// (siehe `shared_default_table`) statt pro Aufruf neu zu rechnen.
+    fn default() -> Self {
+        TabulatedRule { table: *shared_default_table::<R>(), _marker: PhantomData }
+    }
+}
+
+impl<R> RuleSet for TabulatedRule<R>
+        where R: RuleSet<Cell = BinaryCell> {
+    type Cell = self::BinaryCell;
+
+    fn step(&self, neighborhood: [[BinaryCell; 3]; 3]) -> BinaryCell {
+        self.table[pack_neighborhood(neighborhood) as usize]
+    }
+}
+
+/// Zellzustand für `Generations`-Regeln: `0` ist tot, `1` frisch lebendig,
+/// `2..=n` sind absterbende Zwischenzustände, die unabhängig von Geburt/
+/// Überleben durchlaufen werden, bevor die Zelle wieder auf `0` zurückfällt.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GenCell(pub u8);
+
+impl RleCell for GenCell {
+    fn to_rle_char(&self) -> char {
+        match self.0 {
+            0 => '.',
+            n @ 1..=24 => (b'A' + (n - 1)) as char,
+            _ => '?',
+        }
+    }
+
+    fn from_rle_char(token: char) -> Option<Self> {
+        match token {
+            '.' | 'b' => Some(GenCell(0)),
+            'A'..='X' => Some(GenCell((token as u8 - b'A') + 1)),
+            _ => None,
+        }
+    }
+}
+
+/// Laufzeit-konfigurierbare Generations-Regel, angegeben als `"B.../S.../Cn"`
+/// (z.b. `"B2/S/C3"` für eine Brian's-Brain-artige Dynamik). Im Gegensatz zu
+/// `LifeLike` sind Zellen hier nicht nur lebendig/tot, sondern durchlaufen
+/// `states` Alterungsstufen, was die namensgebenden "nachlaufenden" Muster
+/// erzeugt.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Generations {
+    birth: [bool; 9],
+    survival: [bool; 9],
+    states: u8,
+}
+
+impl Generations {
+    /// Parse eine `"B.../S.../Cn"`-Regelangabe wie `"B2/S345/C4"` in eine
+    /// `Generations`-Regel.
+    pub fn parse(s: &str) -> Result<Self, ParseError> {
+        let mut sections = s.splitn(3, '/');
+        let b = sections.next().ok_or(ParseError::MissingSection('B'))?;
+        let s = sections.next().ok_or(ParseError::MissingSection('S'))?;
+        let c = sections.next().ok_or(ParseError::MissingSection('C'))?;
+
+        if !c.starts_with('C') {
+            return Err(ParseError::MissingSection('C'));
+        }
+        let states: u8 = c[1..].parse().map_err(|_| ParseError::InvalidStateCount(c.to_owned()))?;
+        if states < 2 {
+            return Err(ParseError::InvalidStateCount(c.to_owned()));
+        }
+
+        Ok(Generations {
+            birth: parse_neighbor_set(b, 'B')?,
+            survival: parse_neighbor_set(s, 'S')?,
+            states,
+        })
+    }
+}
+
+impl RuleSet for Generations {
+    type Cell = self::GenCell;
+
+    fn step(&self, [[tl, t, tr],
+                [l,  m, r ],
+                [bl, b, br]]: [[GenCell; 3]; 3]) -> GenCell {
+        let newborn_neighbors = [tl, t, tr, l, r, bl, b, br].iter().filter(|c| c.0 == 1).count();
+
+        GenCell(match m.0 {
+            0 if self.birth[newborn_neighbors] => 1,
+            0 => 0,
+            1 if self.survival[newborn_neighbors] => 1,
+            // Bei `states == 2` (n == 1) gibt es keinen Zustand 2: eine nicht
+            // überlebende Zelle stirbt sofort, statt erst noch eine Runde in
+            // einem unerreichbaren Zwischenzustand zu verharren.
+            1 if 2 >= self.states => 0,
+            1 => 2,
+            k if k + 1 >= self.states => 0,
+            k => k + 1,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_conway() {
+        let conway = LifeLike::parse("B3/S23").unwrap();
+        assert_eq!(conway, LifeLike {
+            birth: [false, false, false, true, false, false, false, false, false],
+            survival: [false, false, true, true, false, false, false, false, false],
+        });
+    }
+
+    #[test]
+    fn test_parse_seeds() {
+        let seeds = LifeLike::parse("B2/S").unwrap();
+        assert_eq!(seeds, LifeLike {
+            birth: [false, false, true, false, false, false, false, false, false],
+            survival: [false; 9],
+        });
+    }
+
+    #[test]
+    fn test_parse_missing_section() {
+        assert_eq!(LifeLike::parse("B3"), Err(ParseError::MissingSection('S')));
+    }
+
+    #[test]
+    fn test_parse_invalid_digit() {
+        assert_eq!(LifeLike::parse("B9/S23"), Err(ParseError::InvalidDigit('9')));
+    }
+
+    #[test]
+    fn test_tabulated_rule_matches_game_of_life() {
+        use self::BinaryCell::*;
+
+        let gol = GameOfLife;
+        let tabulated: TabulatedRule<GameOfLife> = TabulatedRule::default();
+
+        let neighborhoods = [
+            [[Dead, Dead, Dead], [Dead, Dead, Dead], [Dead, Dead, Dead]],
+            [[Dead, Live, Dead], [Live, Dead, Live], [Dead, Live, Dead]],
+            [[Live, Live, Live], [Live, Live, Live], [Live, Live, Live]],
+        ];
+
+        for &neighborhood in neighborhoods.iter() {
+            assert_eq!(tabulated.step(neighborhood), gol.step(neighborhood));
+        }
+    }
+
+    #[test]
+    fn test_tabulated_rule_from_parsed_rule() {
+        use self::BinaryCell::*;
+
+        // B36/S23 (HighLife) geboren wird bei genau 3 *oder* 6 Nachbarn; mit
+        // `TabulatedRule::default()` (= `LifeLike::default()`, leere
+        // Birth/Survival-Mengen) würde das nie geboren werden.
+        let highlife = LifeLike::parse("B36/S23").unwrap();
+        let tabulated = TabulatedRule::from_rule(&highlife);
+
+        let six_neighbors = [[Live, Live, Live], [Live, Dead, Live], [Live, Dead, Dead]];
+
+        assert_eq!(highlife.step(six_neighbors), Live);
+        assert_eq!(tabulated.step(six_neighbors), Live);
+    }
+
+    #[test]
+    fn test_parse_brians_brain() {
+        let brain = Generations::parse("B2/S/C3").unwrap();
+        assert_eq!(brain, Generations {
+            birth: [false, false, true, false, false, false, false, false, false],
+            survival: [false; 9],
+            states: 3,
+        });
+    }
+
+    #[test]
+    fn test_parse_invalid_state_count() {
+        assert_eq!(Generations::parse("B2/S/C1"), Err(ParseError::InvalidStateCount("C1".to_owned())));
+        assert_eq!(Generations::parse("B2/S/Cx"), Err(ParseError::InvalidStateCount("Cx".to_owned())));
+    }
+
+    #[test]
+    fn test_generations_step() {
+        let brain = Generations::parse("B2/S/C3").unwrap();
+
+        // Toter Zelle mit genau 2 "neugeborenen" Nachbarn wird geboren.
+        assert_eq!(brain.step([
+            [GenCell(1), GenCell(1), GenCell(0)],
+            [GenCell(0), GenCell(0), GenCell(0)],
+            [GenCell(0), GenCell(0), GenCell(0)],
+        ]), GenCell(1));
+
+        // Neugeborene Zelle, deren Nachbarnanzahl nicht in S liegt (hier ist S
+        // leer), altert in den nächsten Zustand.
+        assert_eq!(brain.step([
+            [GenCell(0), GenCell(0), GenCell(0)],
+            [GenCell(0), GenCell(1), GenCell(0)],
+            [GenCell(0), GenCell(0), GenCell(0)],
+        ]), GenCell(2));
+
+        // Der letzte Zustand fällt zurück auf tot.
+        assert_eq!(brain.step([
+            [GenCell(0), GenCell(0), GenCell(0)],
+            [GenCell(0), GenCell(2), GenCell(0)],
+            [GenCell(0), GenCell(0), GenCell(0)],
+        ]), GenCell(0));
+    }
+
+    #[test]
+    fn test_generations_two_state_dies_immediately() {
+        // Bei C2 (n == 1) gibt es keinen Zustand 2: eine nicht überlebende
+        // lebende Zelle muss direkt auf 0 fallen statt einen Tick in einem
+        // nicht existenten Zwischenzustand zu verbringen.
+        let two_state = Generations::parse("B3/S23/C2").unwrap();
+
+        assert_eq!(two_state.step([
+            [GenCell(0), GenCell(0), GenCell(0)],
+            [GenCell(0), GenCell(1), GenCell(0)],
+            [GenCell(0), GenCell(0), GenCell(0)],
+        ]), GenCell(0));
+    }
+}